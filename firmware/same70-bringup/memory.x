@@ -8,3 +8,19 @@ MEMORY
   /* TODO: This should be 384KiB? */
   RAM : ORIGIN = 0x20401000, LENGTH = 256K
 }
+
+SECTIONS
+{
+  /* DMA descriptors and buffers for the GMAC. This is made non-cacheable
+     by `gmac::dma::configure_dma_region()`, which needs the size to be a power
+     of two, and the start to be aligned to the size. The link fails if the
+     `GmacStorage` does not fit. */
+  .gmac_dma (NOLOAD) : ALIGN(32K)
+  {
+    __sgmac_dma = .;
+    *(.gmac_dma .gmac_dma.*);
+    . = __sgmac_dma + 32K;
+    __egmac_dma = .;
+  } > RAM
+}
+INSERT AFTER .bss;
@@ -3,16 +3,19 @@
 
 use core::ops::Deref;
 
+use cortex_m::singleton;
+
 use same70_bringup::hal::{
     self as _, // global logger + panicking-behavior + memory layout
     efc::Efc,
-    gmac::{Gmac, GmacPins},
+    gmac::{board_mac_addr, phy::Ksz8061, Gmac, GmacConfig, GmacStorage, RmiiPins},
     pio::Pio,
     pmc::{
         ClockSettings, MainClockOscillatorSource, MasterClockSource, MckDivider, MckPrescaler,
         PeripheralIdentifier, Pmc,
     },
     target_device::Peripherals,
+    twihs::{Twihs0, Twihs0Pins},
     wdt::Wdt,
     GlobalRollingTimer,
 };
@@ -49,12 +52,28 @@ fn main() -> ! {
         PeripheralIdentifier::XDMAC,
     ]));
 
+    let pioa_pins = defmt::unwrap!(Pio::new(board.PIOA, &mut pmc)).split();
+    let mut port_a_tok = pioa_pins.token;
     let piod_pins = defmt::unwrap!(Pio::new(board.PIOD, &mut pmc)).split();
     let mut port_d_tok = piod_pins.token;
 
+    // The board's MAC address, from the AT24MAC402 EEPROM
+    let mut twihs0 = defmt::unwrap!(Twihs0::new(
+        board.TWIHS0,
+        100_000,
+        Twihs0Pins {
+            twd: pioa_pins.p03.into_periph_mode_a(&mut port_a_tok),
+            twck: pioa_pins.p04.into_periph_mode_a(&mut port_a_tok),
+        },
+        &mut pmc,
+    ));
+    let mac_addr = board_mac_addr(&mut twihs0, &mut efc);
+
+    let gmac_storage = singleton!(: GmacStorage<8, 4, 1536> = GmacStorage::new()).unwrap();
+
     let mut gmac = defmt::unwrap!(Gmac::new(
         board.GMAC,
-        GmacPins {
+        RmiiPins {
             gtxck: piod_pins.p00.into_periph_mode_a(&mut port_d_tok),
             gtxen: piod_pins.p01.into_periph_mode_a(&mut port_d_tok),
             gtx0: piod_pins.p02.into_periph_mode_a(&mut port_d_tok),
@@ -66,9 +85,10 @@ fn main() -> ! {
             gmdc: piod_pins.p08.into_periph_mode_a(&mut port_d_tok),
             gmdio: piod_pins.p09.into_periph_mode_a(&mut port_d_tok),
         },
+        gmac_storage,
+        &mut Ksz8061::new(0),
         &mut pmc,
-        // 04:91:62:01:02:03
-        [0x04, 0x91, 0x62, 0x01, 0x02, 0x03],
+        GmacConfig::new(mac_addr),
     ));
 
     defmt::println!("Polling...");
@@ -141,12 +161,12 @@ fn main() -> ! {
         // 32: 00, 00, 00, 00, 00, 00,
         // 38: C0, A8, F0, 01,
 
-        // "Our" mac addr: 04-91-62-01-02-03
+        // "Our" mac addr
         wf.iter_mut().for_each(|b| *b = 0);
         wf[0..6].copy_from_slice(&rf[6..12]);
-        wf[6..12].copy_from_slice(&[0x04, 0x91, 0x62, 0x01, 0x02, 0x03]);
+        wf[6..12].copy_from_slice(&mac_addr);
         wf[12..22].copy_from_slice(&[0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x02]);
-        wf[22..28].copy_from_slice(&[0x04, 0x91, 0x62, 0x01, 0x02, 0x03]);
+        wf[22..28].copy_from_slice(&mac_addr);
         wf[28..32].copy_from_slice(&[0xC0, 0xA8, 0xF0, 0x01]);
         wf[32..38].copy_from_slice(&rf[6..12]);
         wf[38..42].copy_from_slice(&rf[28..32]);
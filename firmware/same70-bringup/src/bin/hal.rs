@@ -1,17 +1,19 @@
 #![no_main]
 #![no_std]
 
+use cortex_m::singleton;
 use groundhog::RollingTimer;
 use same70_bringup::hal::{
     self as _,
     efc::Efc,
-    gmac::{Gmac, GmacPins},
+    gmac::{board_mac_addr, phy::Ksz8061, Gmac, GmacConfig, GmacStorage, RmiiPins},
     pio::Pio,
     pmc::{
         ClockSettings, MainClockOscillatorSource, MasterClockSource, MckDivider, MckPrescaler,
         PeripheralIdentifier, Pmc,
     },
     target_device::Peripherals,
+    twihs::{Twihs0, Twihs0Pins},
     wdt::Wdt,
     GlobalRollingTimer,
 }; // global logger + panicking-behavior + memory layout
@@ -51,17 +53,31 @@ fn main() -> ! {
         PeripheralIdentifier::XDMAC,
     ]));
 
-    let _pioa_pins = defmt::unwrap!(Pio::new(board.PIOA, &mut pmc)).split();
+    let pioa_pins = defmt::unwrap!(Pio::new(board.PIOA, &mut pmc)).split();
     let _piob_pins = defmt::unwrap!(Pio::new(board.PIOB, &mut pmc)).split();
     let _pioc_pins = defmt::unwrap!(Pio::new(board.PIOC, &mut pmc)).split();
     let piod_pins = defmt::unwrap!(Pio::new(board.PIOD, &mut pmc)).split();
     let _pioe_pins = defmt::unwrap!(Pio::new(board.PIOE, &mut pmc)).split();
-
+    let mut port_a_tok = pioa_pins.token;
     let mut port_d_tok = piod_pins.token;
 
+    // The board's MAC address, from the AT24MAC402 EEPROM
+    let mut twihs0 = defmt::unwrap!(Twihs0::new(
+        board.TWIHS0,
+        100_000,
+        Twihs0Pins {
+            twd: pioa_pins.p03.into_periph_mode_a(&mut port_a_tok),
+            twck: pioa_pins.p04.into_periph_mode_a(&mut port_a_tok),
+        },
+        &mut pmc,
+    ));
+    let mac_addr = board_mac_addr(&mut twihs0, &mut efc);
+
+    let gmac_storage = singleton!(: GmacStorage<8, 4, 1536> = GmacStorage::new()).unwrap();
+
     let _gmac = Gmac::new(
         board.GMAC,
-        GmacPins {
+        RmiiPins {
             gtxck: piod_pins.p00.into_periph_mode_a(&mut port_d_tok),
             gtxen: piod_pins.p01.into_periph_mode_a(&mut port_d_tok),
             gtx0: piod_pins.p02.into_periph_mode_a(&mut port_d_tok),
@@ -73,9 +89,10 @@ fn main() -> ! {
             gmdc: piod_pins.p08.into_periph_mode_a(&mut port_d_tok),
             gmdio: piod_pins.p09.into_periph_mode_a(&mut port_d_tok),
         },
+        gmac_storage,
+        &mut Ksz8061::new(0),
         &mut pmc,
-        // 04:91:62:01:02:03
-        [0x04, 0x91, 0x62, 0x01, 0x02, 0x03],
+        GmacConfig::new(mac_addr),
     );
 
     defmt::println!("Blankin.");
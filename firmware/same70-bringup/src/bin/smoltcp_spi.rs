@@ -1,11 +1,12 @@
 #![no_main]
 #![no_std]
 
+use core::{mem::MaybeUninit, ptr::addr_of_mut};
 use cortex_m::singleton;
 use groundhog::RollingTimer;
 use same70_bringup::hal::{
     efc::Efc,
-    gmac::{Gmac, GmacPins},
+    gmac::{board_mac_addr, dma, phy::Ksz8061, Gmac, GmacConfig, GmacStorage, RmiiPins},
     pio::Pio,
     pmc::{
         ClockSettings, MainClockOscillatorSource, MasterClockSource, MckDivider, MckPrescaler,
@@ -13,6 +14,7 @@ use same70_bringup::hal::{
     },
     spi::{SelectedTarget, Spi0, Spi0Pins, SpiFreq},
     target_device::Peripherals,
+    twihs::{Twihs0, Twihs0Pins},
     wdt::Wdt,
     GlobalRollingTimer,
 }; // global logger + panicking-behavior + memory layout
@@ -25,10 +27,15 @@ use smoltcp::{
     wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr},
 };
 
+/// The GMAC descriptors and buffers, in the non-cacheable section set up in `memory.x`
+#[link_section = ".gmac_dma"]
+static mut GMAC_STORAGE: MaybeUninit<GmacStorage<8, 4, 1536>> = MaybeUninit::uninit();
+
 #[cortex_m_rt::entry]
 fn main() -> ! {
     // Obtain PAC-level access
     let board = Peripherals::take().unwrap();
+    let mut core = cortex_m::Peripherals::take().unwrap();
 
     let mut efc = Efc::new(board.EFC);
     let mut pmc = Pmc::new(board.PMC);
@@ -58,9 +65,24 @@ fn main() -> ! {
         PeripheralIdentifier::XDMAC,
     ]));
 
+    let pioa_pins = defmt::unwrap!(Pio::new(board.PIOA, &mut pmc)).split();
+    let mut port_a_tok = pioa_pins.token;
     let piod_pins = defmt::unwrap!(Pio::new(board.PIOD, &mut pmc)).split();
     let mut port_d_tok = piod_pins.token;
 
+    // The board's MAC address, from the AT24MAC402 EEPROM. This must be
+    // read before the data cache is enabled.
+    let mut twihs0 = defmt::unwrap!(Twihs0::new(
+        board.TWIHS0,
+        100_000,
+        Twihs0Pins {
+            twd: pioa_pins.p03.into_periph_mode_a(&mut port_a_tok),
+            twck: pioa_pins.p04.into_periph_mode_a(&mut port_a_tok),
+        },
+        &mut pmc,
+    ));
+    let mac_addr = board_mac_addr(&mut twihs0, &mut efc);
+
     let spi_pins = Spi0Pins {
         miso: piod_pins.p20.into_periph_mode_b(&mut port_d_tok),
         mosi: piod_pins.p21.into_periph_mode_b(&mut port_d_tok),
@@ -69,9 +91,17 @@ fn main() -> ! {
     };
     let mut spi = defmt::unwrap!(Spi0::new(board.SPI0, SpiFreq::M10_0, spi_pins, &mut pmc,));
 
+    // Make the GMAC's DMA memory non-cacheable, so the caches can be enabled
+    defmt::unwrap!(dma::configure_dma_region(&mut core.MPU, 0));
+    core.SCB.enable_icache();
+    core.SCB.enable_dcache(&mut core.CPUID);
+
+    let gmac_storage = GmacStorage::init(unsafe { &mut *addr_of_mut!(GMAC_STORAGE) });
+    let mut phy = Ksz8061::new(0);
+
     let gmac = defmt::unwrap!(Gmac::new(
         board.GMAC,
-        GmacPins {
+        RmiiPins {
             gtxck: piod_pins.p00.into_periph_mode_a(&mut port_d_tok),
             gtxen: piod_pins.p01.into_periph_mode_a(&mut port_d_tok),
             gtx0: piod_pins.p02.into_periph_mode_a(&mut port_d_tok),
@@ -83,9 +113,10 @@ fn main() -> ! {
             gmdc: piod_pins.p08.into_periph_mode_a(&mut port_d_tok),
             gmdio: piod_pins.p09.into_periph_mode_a(&mut port_d_tok),
         },
+        gmac_storage,
+        &mut phy,
         &mut pmc,
-        // 04:91:62:01:02:03
-        [0x04, 0x91, 0x62, 0x01, 0x02, 0x03],
+        GmacConfig::new(mac_addr),
     ));
 
     let ip_addrs: &'static mut _ = singleton!(: [IpCidr; 1] = [
@@ -100,8 +131,10 @@ fn main() -> ! {
         singleton!(: [Option<(IpCidr, Route)>; 1] = [None; 1]).unwrap();
     let routes = Routes::new(routes_storage.as_mut_slice());
 
+    let mac_addr = gmac.mac_addr();
+
     let mut iface = InterfaceBuilder::new(gmac, sockets.as_mut_slice())
-        .hardware_addr(EthernetAddress::from_bytes(&[0x04, 0x91, 0x62, 0x01, 0x02, 0x03]).into())
+        .hardware_addr(EthernetAddress::from_bytes(&mac_addr).into())
         .neighbor_cache(NeighborCache::new(neighbor_cache.as_mut_slice()))
         .routes(routes)
         .ip_addrs(ip_addrs.as_mut_slice())
@@ -126,10 +159,25 @@ fn main() -> ! {
 
     let mut last_state = smoltcp::socket::TcpState::Closed;
 
+    let mut last_link_poll = timer.get_ticks();
+
     loop {
         // Log any relevant events
         iface.device_mut().query();
 
+        // Check the link periodically, and restart DHCP whenever it changes
+        if timer.millis_since(last_link_poll) >= 100 {
+            last_link_poll = timer.get_ticks();
+            match iface.device_mut().poll_link(&mut phy) {
+                Ok(Some(event)) => {
+                    defmt::println!("Link event: {}", event);
+                    iface.get_socket::<Dhcpv4Socket>(dhcp_handle).reset();
+                }
+                Ok(None) => {}
+                Err(e) => defmt::println!("PHY error: {}", e),
+            }
+        }
+
         // TODO: This will roll over after 145 hours!
         match iface.poll(Instant::from_micros(timer.micros_since(start))) {
             Ok(_) => {}
@@ -0,0 +1,321 @@
+//! Turn a GMAC frame capture stream into a `.pcap` file
+//!
+//! The stream written to a `PcapSink` by the HAL is already in the pcap
+//! format, but it may be preceded by unrelated data, cut off, or restarted by
+//! the firmware. This finds the start of the stream, checks each record, and
+//! writes a `.pcap` file that Wireshark can open, or follow when written to
+//! stdout.
+//!
+//! ```text
+//! pcap-dump [INPUT] [OUTPUT]        read the stream from a file, or stdin
+//! pcap-dump --udp ADDR [OUTPUT]     receive the stream as UDP datagrams
+//! ```
+//!
+//! `-` (the default) stands for stdin or stdout. For example:
+//!
+//! ```text
+//! pcap-dump --udp 0.0.0.0:5555 | wireshark -k -i -
+//! ```
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::UdpSocket;
+use std::process::exit;
+
+const PCAP_MAGIC: u32 = 0xA1B2_C3D4;
+const HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+const USAGE: &str = "usage: pcap-dump [INPUT] [OUTPUT]\n       pcap-dump --udp ADDR [OUTPUT]";
+
+/// Copies valid records of a capture stream into a pcap file
+struct Converter<W: Write> {
+    out: W,
+    buf: Vec<u8>,
+    /// The snapshot length of the current stream, once its header was found
+    snaplen: Option<u32>,
+    header_written: bool,
+    records: u64,
+    skipped: u64,
+}
+
+impl<W: Write> Converter<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            buf: Vec::new(),
+            snaplen: None,
+            header_written: false,
+            records: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Process the next bytes of the stream
+    fn feed(&mut self, data: &[u8]) -> io::Result<()> {
+        self.buf.extend_from_slice(data);
+        let mut pos = 0;
+
+        loop {
+            let rest = &self.buf[pos..];
+            let snaplen = match self.snaplen {
+                Some(snaplen) => snaplen,
+                None => {
+                    // Skip anything up to the next file header. A partial magic
+                    // number may be at the very end.
+                    let off = find_magic(rest).unwrap_or(rest.len().saturating_sub(3));
+                    self.skipped += off as u64;
+                    pos += off;
+                    let rest = &self.buf[pos..];
+                    if rest.len() < HEADER_LEN {
+                        break;
+                    }
+
+                    // Only the first header goes into the file
+                    if !self.header_written {
+                        self.out.write_all(&rest[..HEADER_LEN])?;
+                        self.header_written = true;
+                    }
+                    self.snaplen = Some(le32(&rest[16..20]));
+                    pos += HEADER_LEN;
+                    continue;
+                }
+            };
+
+            if rest.len() < RECORD_HEADER_LEN {
+                break;
+            }
+            if le32(&rest[0..4]) == PCAP_MAGIC {
+                // The firmware restarted the capture
+                self.snaplen = None;
+                continue;
+            }
+
+            let micros = le32(&rest[4..8]);
+            let incl_len = le32(&rest[8..12]);
+            let orig_len = le32(&rest[12..16]);
+            if (micros >= 1_000_000) || (incl_len > snaplen) || (incl_len > orig_len) {
+                eprintln!("pcap-dump: invalid record, waiting for the next capture");
+                self.snaplen = None;
+                self.skipped += 1;
+                pos += 1;
+                continue;
+            }
+
+            let len = RECORD_HEADER_LEN + incl_len as usize;
+            if rest.len() < len {
+                break;
+            }
+            self.out.write_all(&rest[..len])?;
+            self.out.flush()?;
+            self.records += 1;
+            pos += len;
+        }
+
+        self.buf.drain(..pos);
+        Ok(())
+    }
+}
+
+fn find_magic(data: &[u8]) -> Option<usize> {
+    let magic = PCAP_MAGIC.to_le_bytes();
+    data.windows(magic.len()).position(|w| w == magic)
+}
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn run(args: &[String]) -> io::Result<()> {
+    let (udp, paths) = match args.first().map(String::as_str) {
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Some("--udp") => match args.get(1) {
+            Some(addr) => (Some(addr.as_str()), &args[2..]),
+            None => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        },
+        _ => (None, args),
+    };
+    let max_paths = if udp.is_some() { 1 } else { 2 };
+    if paths.len() > max_paths {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+    let (input, output) = if udp.is_some() {
+        ("-", paths.first())
+    } else {
+        (paths.first().map_or("-", String::as_str), paths.get(1))
+    };
+
+    let out: Box<dyn Write> = match output.map(String::as_str) {
+        None | Some("-") => Box::new(io::stdout()),
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+    };
+    let mut conv = Converter::new(out);
+    let mut buf = vec![0; 64 * 1024];
+
+    if let Some(addr) = udp {
+        let socket = UdpSocket::bind(addr)?;
+        eprintln!("pcap-dump: listening on {}", socket.local_addr()?);
+        loop {
+            let len = socket.recv(&mut buf)?;
+            conv.feed(&buf[..len])?;
+        }
+    }
+
+    let mut reader: Box<dyn Read> = match input {
+        "-" => Box::new(io::stdin()),
+        path => Box::new(File::open(path)?),
+    };
+    loop {
+        let len = reader.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        conv.feed(&buf[..len])?;
+    }
+
+    conv.out.flush()?;
+    eprintln!(
+        "pcap-dump: {} frames written, {} bytes skipped",
+        conv.records, conv.skipped
+    );
+    if !conv.header_written {
+        eprintln!("pcap-dump: no capture found in the input");
+        exit(1);
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("pcap-dump: {}", e);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_header() -> Vec<u8> {
+        let mut header = PCAP_MAGIC.to_le_bytes().to_vec();
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&0xFFFFu32.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header
+    }
+
+    fn record(secs: u32, frame: &[u8]) -> Vec<u8> {
+        let mut record = secs.to_le_bytes().to_vec();
+        record.extend_from_slice(&500_000u32.to_le_bytes());
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        record.extend_from_slice(frame);
+        record
+    }
+
+    fn convert(chunks: &[&[u8]]) -> Converter<Vec<u8>> {
+        let mut conv = Converter::new(Vec::new());
+        for chunk in chunks {
+            conv.feed(chunk).unwrap();
+        }
+        conv
+    }
+
+    #[test]
+    fn garbage_before_header() {
+        let garbage = b"boot log\r\n\xA1\xB2";
+        let stream = [&garbage[..], &file_header(), &record(1, &[0xAA; 60])].concat();
+
+        let conv = convert(&[&stream]);
+        assert_eq!(conv.out, [file_header(), record(1, &[0xAA; 60])].concat());
+        assert_eq!(conv.records, 1);
+        assert_eq!(conv.skipped, garbage.len() as u64);
+    }
+
+    #[test]
+    fn restarted_stream() {
+        let stream = [
+            file_header(),
+            record(1, &[0x11; 60]),
+            file_header(),
+            record(0, &[0x22; 64]),
+        ]
+        .concat();
+
+        // Only the first file header is kept
+        let conv = convert(&[&stream]);
+        assert_eq!(
+            conv.out,
+            [
+                file_header(),
+                record(1, &[0x11; 60]),
+                record(0, &[0x22; 64])
+            ]
+            .concat()
+        );
+        assert_eq!(conv.records, 2);
+    }
+
+    #[test]
+    fn truncated_stream() {
+        let full = record(2, &[0x33; 100]);
+        let stream = [file_header(), record(1, &[0x11; 60]), full[..40].to_vec()].concat();
+
+        // The partial record is held back, waiting for the rest
+        let conv = convert(&[&stream]);
+        assert_eq!(conv.out, [file_header(), record(1, &[0x11; 60])].concat());
+        assert_eq!(conv.records, 1);
+    }
+
+    #[test]
+    fn truncated_then_restarted() {
+        let cut = record(2, &[0x33; 100]);
+        let stream = [
+            file_header(),
+            record(1, &[0x11; 60]),
+            cut[..6].to_vec(),
+            file_header(),
+            record(0, &[0x44; 60]),
+        ]
+        .concat();
+
+        let conv = convert(&[&stream]);
+        assert_eq!(
+            conv.out,
+            [
+                file_header(),
+                record(1, &[0x11; 60]),
+                record(0, &[0x44; 60])
+            ]
+            .concat()
+        );
+        assert_eq!(conv.records, 2);
+    }
+
+    #[test]
+    fn split_into_small_chunks() {
+        let stream = [
+            b"xyz".to_vec(),
+            file_header(),
+            record(1, &[0x11; 60]),
+            record(2, &[0x22; 70]),
+        ]
+        .concat();
+
+        let chunks: Vec<&[u8]> = stream.chunks(5).collect();
+        let conv = convert(&chunks);
+        assert_eq!(conv.out, stream[3..]);
+        assert_eq!(conv.records, 2);
+    }
+}